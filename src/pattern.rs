@@ -1,4 +1,5 @@
 use std::fmt;
+use std::rc::Rc;
 
 use itertools::Itertools;
 use log::*;
@@ -11,6 +12,13 @@ use crate::{Applier, EGraph, ENode, Id, Language, Metadata, QuestionMarkName, Re
 pub enum Pattern<L> {
     ENode(Box<ENode<L, Pattern<L>>>),
     Wildcard(QuestionMarkName, WildcardKind),
+    /// Matches any of its alternatives, e.g. `(neg (| (+ ?a ?b) (* ?a ?b)))`, so a rule LHS can
+    /// cover several shapes without duplicating the whole rule.
+    Or(Vec<Pattern<L>>),
+    /// Like `Wildcard`, but only binds to an e-class that has an e-node satisfying `OpFilter`,
+    /// e.g. "only a constant e-node" or "only ops in `{+, *}`". Pruned during search rather than
+    /// filtered afterwards in a guard.
+    TypedWildcard(QuestionMarkName, WildcardKind, OpFilter<L>),
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Hash)]
@@ -19,6 +27,52 @@ pub enum WildcardKind {
     ZeroOrMore,
 }
 
+/// A predicate restricting what a [`Pattern::TypedWildcard`] may bind to, checked against an
+/// e-class's e-nodes: the wildcard matches if *any* e-node in the e-class satisfies it.
+#[derive(Clone)]
+pub struct OpFilter<L> {
+    name: String,
+    pred: Rc<dyn Fn(&ENode<L, Id>) -> bool>,
+}
+
+impl<L> OpFilter<L> {
+    pub fn new(name: impl Into<String>, pred: impl Fn(&ENode<L, Id>) -> bool + 'static) -> Self {
+        OpFilter {
+            name: name.into(),
+            pred: Rc::new(pred),
+        }
+    }
+
+    /// Matches only e-classes containing a constant e-node, i.e. one with no children.
+    pub fn constant() -> Self {
+        OpFilter::new("constant", |e| e.children.is_empty())
+    }
+
+    fn matches(&self, e: &ENode<L, Id>) -> bool {
+        (self.pred)(e)
+    }
+}
+
+impl<L: PartialEq + Clone + 'static> OpFilter<L> {
+    /// Matches only e-classes whose head op is one of `ops`, e.g. `{+, *}`.
+    pub fn ops(name: impl Into<String>, ops: Vec<L>) -> Self {
+        OpFilter::new(name, move |e| ops.contains(&e.op))
+    }
+}
+
+impl<L> fmt::Debug for OpFilter<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OpFilter").field(&self.name).finish()
+    }
+}
+
+// compared by name: the predicate closure itself isn't comparable
+impl<L> PartialEq for OpFilter<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
 impl<L: Language> Pattern<L> {
     pub fn from_expr(e: &RecExpr<L>) -> Self {
         Pattern::ENode(
@@ -35,6 +89,14 @@ impl<L: Language> Pattern<L> {
                 let msg = format!("Found wildcard {:?} instead of expr term", w);
                 Err(msg)
             }
+            Pattern::Or(_) => {
+                let msg = "Found an Or pattern instead of expr term".to_string();
+                Err(msg)
+            }
+            Pattern::TypedWildcard(w, ..) => {
+                let msg = format!("Found typed wildcard {:?} instead of expr term", w);
+                Err(msg)
+            }
         }
     }
 
@@ -54,10 +116,15 @@ impl<L: Language> Pattern<L> {
                 assert_eq!(*kind, WildcardKind::Single);
                 mapping.get(w, *kind).unwrap()[0]
             }
+            Pattern::TypedWildcard(w, kind, _) => {
+                assert_eq!(*kind, WildcardKind::Single);
+                mapping.get(w, *kind).unwrap()[0]
+            }
             Pattern::ENode(expr) => {
                 let expr = expr.map_children(|pat| pat.subst_and_find(egraph, mapping));
                 egraph.add(expr)
             }
+            Pattern::Or(_) => panic!("Found an Or pattern where a concrete term was expected"),
         }
     }
 
@@ -92,6 +159,12 @@ impl<L: Language + fmt::Display> Pattern<L> {
                     Sexp::List(vec)
                 }
             },
+            Pattern::Or(alts) => {
+                let mut vec: Vec<_> = alts.iter().map(Self::to_sexp).collect();
+                vec.insert(0, Sexp::String("|".to_string()));
+                Sexp::List(vec)
+            }
+            Pattern::TypedWildcard(w, _, filter) => Sexp::String(format!("{}:{}", w, filter.name)),
         }
     }
 }
@@ -179,6 +252,55 @@ impl<L: Language, M: Metadata<L>> Applier<L, M> for Pattern<L> {
     }
 }
 
+/// A side-condition checked against the bindings a [`Searcher`] produced, the way a guarded
+/// match arm stores a predicate evaluated after the structural pattern already succeeded.
+pub trait Condition<L, M> {
+    fn check(&self, egraph: &mut EGraph<L, M>, eclass: Id, subst: &WildMap) -> bool;
+}
+
+/// An [`Applier`] that only instantiates its RHS when `condition` holds for the captured
+/// bindings, e.g. "only when `?n` is a nonzero constant" or "only when `?a` and `?b` are
+/// already in the same e-class". Returns no new e-classes when the guard fails.
+pub struct ConditionalApplier<C, A> {
+    pub condition: C,
+    pub applier: A,
+}
+
+impl<L, M, C, A> Applier<L, M> for ConditionalApplier<C, A>
+where
+    L: Language,
+    M: Metadata<L>,
+    C: Condition<L, M>,
+    A: Applier<L, M>,
+{
+    fn apply_one(&self, egraph: &mut EGraph<L, M>, eclass: Id, subst: &WildMap) -> Vec<Id> {
+        if self.condition.check(egraph, eclass, subst) {
+            self.applier.apply_one(egraph, eclass, subst)
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// A [`Condition`] that holds when both patterns, substituted with the current bindings,
+/// resolve to the same e-class.
+pub struct ConditionEqual<L>(pub Pattern<L>, pub Pattern<L>);
+
+impl<L: Language, M: Metadata<L>> Condition<L, M> for ConditionEqual<L> {
+    fn check(&self, egraph: &mut EGraph<L, M>, _eclass: Id, subst: &WildMap) -> bool {
+        self.0.subst_and_find(egraph, subst) == self.1.subst_and_find(egraph, subst)
+    }
+}
+
+impl<L, M, F> Condition<L, M> for F
+where
+    F: Fn(&mut EGraph<L, M>, Id, &WildMap) -> bool,
+{
+    fn check(&self, egraph: &mut EGraph<L, M>, eclass: Id, subst: &WildMap) -> bool {
+        self(egraph, eclass, subst)
+    }
+}
+
 fn search_pat<L: Language, M>(
     pat: &Pattern<L>,
     depth: usize,
@@ -186,6 +308,17 @@ fn search_pat<L: Language, M>(
     eclass: Id,
 ) -> SmallVec<[WildMap; 1]> {
     let pat_expr = match pat {
+        Pattern::Or(alts) => {
+            // each alternative may bind a different subset of wildcards; union their matches
+            // rather than requiring them to agree. Callers that pair this LHS with an RHS
+            // should validate with `check_or_bindings` first; `apply_pat` degrades gracefully
+            // (no new e-classes) if that was skipped and the RHS reads an unbound wildcard.
+            let mut mappings = SmallVec::new();
+            for alt in alts {
+                mappings.extend(search_pat(alt, depth, egraph, eclass));
+            }
+            return mappings;
+        }
         Pattern::Wildcard(w, kind) => {
             assert_eq!(*kind, WildcardKind::Single);
             let mut var_mapping = WildMap::default();
@@ -194,6 +327,18 @@ fn search_pat<L: Language, M>(
 
             return smallvec![var_mapping];
         }
+        Pattern::TypedWildcard(w, kind, filter) => {
+            assert_eq!(*kind, WildcardKind::Single);
+            if !egraph[eclass].iter().any(|e| filter.matches(e)) {
+                // no e-node in the e-class satisfies the filter: prune this whole branch
+                return SmallVec::new();
+            }
+            let mut var_mapping = WildMap::default();
+            let was_there = var_mapping.insert(w.clone(), *kind, vec![eclass]);
+            assert_eq!(was_there, None);
+
+            return smallvec![var_mapping];
+        }
         Pattern::ENode(e) => e,
     };
 
@@ -208,59 +353,72 @@ fn search_pat<L: Language, M>(
         }
     } else {
         for e in egraph[eclass].iter().filter(|e| e.op == pat_expr.op) {
-            let n_multi = pat_expr
+            let multis: Vec<_> = pat_expr
                 .children
                 .iter()
-                .filter(|p| p.is_multi_wildcard())
-                .count();
-            let (range, multi_mapping) = if n_multi > 0 {
-                assert_eq!(n_multi, 1, "Patterns can only have one multi match");
-                let (position, q) = pat_expr
-                    .children
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, p)| match p {
-                        Pattern::Wildcard(q, WildcardKind::ZeroOrMore) => Some((i, q)),
-                        Pattern::Wildcard(_, WildcardKind::Single) => None,
-                        Pattern::ENode(_) => None,
-                    })
-                    .next()
-                    .unwrap();
-                assert_eq!(
-                    position,
-                    pat_expr.children.len() - 1,
-                    "Multi matches must be in the tail position for now"
-                );
-
-                // if the pattern is more than one longer, then we
-                // can't match the multi matcher
-                let len = pat_expr.children.len();
-                if len - 1 > e.children.len() {
+                .enumerate()
+                .filter_map(|(i, p)| match p {
+                    Pattern::Wildcard(q, WildcardKind::ZeroOrMore) => Some((i, q, None)),
+                    Pattern::Wildcard(_, WildcardKind::Single) => None,
+                    Pattern::TypedWildcard(q, WildcardKind::ZeroOrMore, filter) => {
+                        Some((i, q, Some(filter)))
+                    }
+                    Pattern::TypedWildcard(_, WildcardKind::Single, _) => None,
+                    Pattern::ENode(_) => None,
+                    Pattern::Or(_) => None,
+                })
+                .collect();
+            assert!(multis.len() <= 1, "Patterns can only have one multi match");
+
+            let mut arg_mappings: Vec<SmallVec<[WildMap; 1]>> = Vec::new();
+
+            if let Some(&(position, q, filter)) = multis.first() {
+                // slice-pattern semantics: `position` fixed patterns come before the
+                // multi-wildcard and `fixed_len - position` come after it, so the e-node needs
+                // at least `fixed_len` children for any split to exist.
+                let fixed_len = pat_expr.children.len() - 1;
+                if fixed_len > e.children.len() {
                     continue;
                 }
-                let ids = e.children[len - 1..].to_vec();
-                (
-                    (0..len - 1),
-                    Some((q.clone(), WildcardKind::ZeroOrMore, ids)),
-                )
+                let suffix_len = fixed_len - position;
+                let m = e.children.len();
+
+                for (pa, ea) in pat_expr.children[..position].iter().zip(&e.children[..position])
+                {
+                    arg_mappings.push(search_pat(pa, depth + 1, egraph, *ea));
+                }
+
+                let multi_ids = e.children[position..m - suffix_len].to_vec();
+                if let Some(filter) = filter {
+                    // a typed multi-wildcard refuses to bind if any captured e-class fails the
+                    // filter, same as a typed single wildcard does in the arm above
+                    let all_match = multi_ids
+                        .iter()
+                        .all(|&id| egraph[id].iter().any(|n| filter.matches(n)));
+                    if !all_match {
+                        continue;
+                    }
+                }
+                let mut multi_map = WildMap::default();
+                multi_map
+                    .vec
+                    .push((q.clone(), WildcardKind::ZeroOrMore, multi_ids));
+                arg_mappings.push(smallvec![multi_map]);
+
+                for (pa, ea) in pat_expr.children[position + 1..]
+                    .iter()
+                    .zip(&e.children[m - suffix_len..])
+                {
+                    arg_mappings.push(search_pat(pa, depth + 1, egraph, *ea));
+                }
             } else {
                 let len = pat_expr.children.len();
                 if len != e.children.len() {
                     continue;
                 }
-                ((0..len), None)
-            };
-
-            let mut arg_mappings: Vec<_> = pat_expr.children[range]
-                .iter()
-                .zip(&e.children)
-                .map(|(pa, ea)| search_pat(pa, depth + 1, egraph, *ea))
-                .collect();
-
-            if let Some((q, kind, ids)) = multi_mapping {
-                let mut m = WildMap::default();
-                m.vec.push((q, kind, ids));
-                arg_mappings.push(smallvec![m]);
+                for (pa, ea) in pat_expr.children.iter().zip(&e.children) {
+                    arg_mappings.push(search_pat(pa, depth + 1, egraph, *ea));
+                }
             }
 
             'outer: for ms in arg_mappings.iter().multi_cartesian_product() {
@@ -283,28 +441,608 @@ fn search_pat<L: Language, M>(
     new_mappings
 }
 
+/// Instantiates `pat` under `mapping`, or produces no e-classes if `pat` reads a wildcard
+/// `mapping` doesn't bind. That can only happen for an RHS paired with an `Or` LHS whose
+/// alternatives bind different wildcard subsets and wasn't checked with [`check_or_bindings`]
+/// at rule-construction time; treating it like a failed [`Condition`] (no new e-classes) is
+/// safer than panicking on otherwise-valid input.
 fn apply_pat<L: Language, M: Metadata<L>>(
     pat: &Pattern<L>,
     egraph: &mut EGraph<L, M>,
     mapping: &WildMap,
 ) -> Vec<Id> {
+    apply_pat_rec(pat, egraph, mapping).unwrap_or_default()
+}
+
+fn apply_pat_rec<L: Language, M: Metadata<L>>(
+    pat: &Pattern<L>,
+    egraph: &mut EGraph<L, M>,
+    mapping: &WildMap,
+) -> Option<Vec<Id>> {
     trace!("apply_rec {:2?} {:?}", pat, mapping);
 
     let result = match &pat {
-        Pattern::Wildcard(w, kind) => mapping.get(&w, *kind).unwrap().iter().copied().collect(),
+        Pattern::Wildcard(w, kind) => mapping.get(w, *kind)?.to_vec(),
+        Pattern::TypedWildcard(w, kind, _) => mapping.get(w, *kind)?.to_vec(),
         Pattern::ENode(e) => {
-            let children = e
-                .children
-                .iter()
-                .flat_map(|child| apply_pat(child, egraph, mapping));
+            let mut children = Vec::new();
+            for child in &e.children {
+                children.extend(apply_pat_rec(child, egraph, mapping)?);
+            }
             let n = ENode::new(e.op.clone(), children);
             trace!("adding: {:?}", n);
             vec![egraph.add(n)]
         }
+        Pattern::Or(_) => panic!("Found an Or pattern on the RHS; Or is only supported in LHSs"),
     };
 
     trace!("result: {:?}", result);
-    result
+    Some(result)
+}
+
+/// A single row being compiled: the patterns still left to match for `rule`, in the order
+/// they'll be consumed (front = next), along with the index of the original pattern it came
+/// from inside the slice passed to [`PatternProgram::compile`].
+struct Row<L> {
+    rule: usize,
+    cols: Vec<Pattern<L>>,
+}
+
+/// A shared decision tree compiled from many [`Pattern`]s, so a `Runner` holding dozens of
+/// rewrites can search an e-class once instead of re-walking it per rule.
+///
+/// This is built the way a pattern-match compiler turns many `match` arms into one switch:
+/// each pattern is a row, each structural position an e-class is a column, and at every node
+/// we pick an unresolved column and branch on the operators that appear there across the
+/// surviving rows.
+///
+/// Patterns containing a [`WildcardKind::ZeroOrMore`] wildcard, a [`Pattern::Or`] or a
+/// [`Pattern::TypedWildcard`] aren't folded into the tree (see [`needs_naive_search`]), so
+/// [`MultiPattern`] falls back to [`search_pat`] for those.
+#[derive(Debug)]
+pub enum PatternProgram<L> {
+    /// No columns remain for any row that reached this point; `rules` lists which of the
+    /// compiled patterns (by index) matched.
+    Leaf(Vec<usize>),
+    Switch {
+        /// One subtree per operator appearing in the surviving rows' leading column, explored
+        /// once per e-node in the e-class that has that op and arity.
+        cases: Vec<(L, usize, Box<PatternProgram<L>>)>,
+        /// Rows whose leading column is a wildcard: bind `name` to the whole e-class for
+        /// `rule`, independent of which operator (if any) the e-class's e-nodes carry.
+        wildcards: Vec<(QuestionMarkName, usize)>,
+        /// Continuation shared by every row in `wildcards`, since a wildcard consumes no
+        /// children and all such rows proceed with the same remaining columns.
+        default: Option<Box<PatternProgram<L>>>,
+    },
+}
+
+impl<L: Language> PatternProgram<L> {
+    /// Compile `rules` into a shared decision tree. `rules` must not contain patterns for which
+    /// [`needs_naive_search`] holds: `build` panics on them. [`MultiPattern::compile`] filters
+    /// those out and handles them via direct search.
+    fn compile(rules: impl Iterator<Item = (usize, Pattern<L>)>) -> Self {
+        let rows = rules
+            .map(|(rule, pat)| Row {
+                rule,
+                cols: vec![pat],
+            })
+            .collect();
+        Self::build(rows)
+    }
+
+    fn build(rows: Vec<Row<L>>) -> Self {
+        if rows.iter().all(|r| r.cols.is_empty()) {
+            return PatternProgram::Leaf(rows.into_iter().map(|r| r.rule).collect());
+        }
+
+        let mut wildcards = Vec::new();
+        let mut default_rows = Vec::new();
+        // preserves insertion order so compilation is deterministic
+        let mut case_groups: Vec<(L, usize, Vec<Row<L>>)> = Vec::new();
+
+        for row in rows {
+            let mut cols = row.cols;
+            let head = cols.remove(0);
+            match head {
+                Pattern::Wildcard(name, WildcardKind::Single) => {
+                    wildcards.push((name, row.rule));
+                    default_rows.push(Row {
+                        rule: row.rule,
+                        cols,
+                    });
+                }
+                Pattern::Wildcard(_, WildcardKind::ZeroOrMore) => {
+                    unreachable!("multi-wildcard patterns are filtered out before compiling")
+                }
+                Pattern::Or(_) => {
+                    unreachable!("Or patterns are filtered out before compiling")
+                }
+                Pattern::TypedWildcard(..) => {
+                    unreachable!("typed wildcard patterns are filtered out before compiling")
+                }
+                Pattern::ENode(e) => {
+                    let arity = e.children.len();
+                    let mut new_cols = e.children;
+                    new_cols.extend(cols);
+                    match case_groups
+                        .iter_mut()
+                        .find(|(op, ar, _)| *op == e.op && *ar == arity)
+                    {
+                        Some((_, _, group_rows)) => group_rows.push(Row {
+                            rule: row.rule,
+                            cols: new_cols,
+                        }),
+                        None => case_groups.push((
+                            e.op,
+                            arity,
+                            vec![Row {
+                                rule: row.rule,
+                                cols: new_cols,
+                            }],
+                        )),
+                    }
+                }
+            }
+        }
+
+        let cases = case_groups
+            .into_iter()
+            .map(|(op, arity, rows)| (op, arity, Box::new(Self::build(rows))))
+            .collect();
+        let default = if default_rows.is_empty() {
+            None
+        } else {
+            Some(Box::new(Self::build(default_rows)))
+        };
+
+        PatternProgram::Switch {
+            cases,
+            wildcards,
+            default,
+        }
+    }
+
+    /// Walk the tree against the e-classes in `ids` (front = next column), appending matches
+    /// for each live `(rule, bindings-so-far)` pair to `out[rule]`.
+    fn eval<M: Metadata<L>>(
+        &self,
+        egraph: &EGraph<L, M>,
+        ids: &[Id],
+        live: &[(usize, WildMap)],
+        out: &mut [Vec<WildMap>],
+    ) {
+        match self {
+            PatternProgram::Leaf(rules) => {
+                for rule in rules {
+                    // `rule` may be absent from `live` if a repeated wildcard mismatched higher
+                    // up and dropped it from `new_live`; that rule simply didn't match here.
+                    if let Some((_, wm)) = live.iter().find(|(r, _)| r == rule) {
+                        out[*rule].push(wm.clone());
+                    }
+                }
+            }
+            PatternProgram::Switch {
+                cases,
+                wildcards,
+                default,
+            } => {
+                let (&current, rest) = ids.split_first().expect("id queue exhausted early");
+
+                if let Some(default) = default {
+                    let new_live: Vec<_> = wildcards
+                        .iter()
+                        .filter_map(|(name, rule)| {
+                            // `rule` may already be missing from `live` if an earlier repeated
+                            // wildcard mismatched further up this same column chain.
+                            let mut wm = live.iter().find(|(r, _)| r == rule)?.1.clone();
+                            // a repeated wildcard (e.g. `(- ?a ?a)`) must rebind to the same
+                            // e-class every time, just like `search_pat`'s `combined.insert` check
+                            if let Some(old_ids) = wm.insert(name.clone(), WildcardKind::Single, vec![current])
+                            {
+                                if old_ids != [current] {
+                                    return None;
+                                }
+                            }
+                            Some((*rule, wm))
+                        })
+                        .collect();
+                    default.eval(egraph, rest, &new_live, out);
+                }
+
+                for (op, arity, subtree) in cases {
+                    for e in egraph[current]
+                        .iter()
+                        .filter(|e| e.op == *op && e.children.len() == *arity)
+                    {
+                        let mut new_ids = e.children.clone();
+                        new_ids.extend_from_slice(rest);
+                        subtree.eval(egraph, &new_ids, live, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A set of [`Pattern`]s compiled into one [`PatternProgram`] so a `Runner` with many rewrites
+/// can search each e-class once instead of once per rule.
+#[derive(Debug)]
+pub struct MultiPattern<L> {
+    program: PatternProgram<L>,
+    tree_rules: Vec<usize>,
+    // patterns with a multi-wildcard, an Or or a typed wildcard, matched directly since they
+    // don't fold into `program`
+    multi: Vec<(usize, Pattern<L>)>,
+    len: usize,
+}
+
+impl<L: Language> MultiPattern<L> {
+    pub fn compile(patterns: &[Pattern<L>]) -> Self {
+        let mut tree_rules = Vec::new();
+        let mut multi = Vec::new();
+        for (i, pat) in patterns.iter().enumerate() {
+            if needs_naive_search(pat) {
+                multi.push((i, pat.clone()));
+            } else {
+                tree_rules.push(i);
+            }
+        }
+        let program = PatternProgram::compile(
+            tree_rules
+                .iter()
+                .map(|&i| (i, patterns[i].clone())),
+        );
+        MultiPattern {
+            program,
+            tree_rules,
+            multi,
+            len: patterns.len(),
+        }
+    }
+
+    /// Search every e-class once, returning one `Vec<SearchMatches>` per input pattern (indexed
+    /// the same way as the slice passed to [`MultiPattern::compile`]).
+    pub fn search<M: Metadata<L>>(&self, egraph: &EGraph<L, M>) -> Vec<Vec<SearchMatches>> {
+        let mut per_rule: Vec<Vec<SearchMatches>> = (0..self.len).map(|_| Vec::new()).collect();
+        for class in egraph.classes() {
+            let mut mappings: Vec<Vec<WildMap>> = (0..self.len).map(|_| Vec::new()).collect();
+
+            let live: Vec<_> = self
+                .tree_rules
+                .iter()
+                .map(|&r| (r, WildMap::default()))
+                .collect();
+            self.program.eval(egraph, &[class.id], &live, &mut mappings);
+
+            for (rule, pat) in &self.multi {
+                if let Some(sm) = pat.search_eclass(egraph, class.id) {
+                    mappings[*rule].extend(sm.mappings);
+                }
+            }
+
+            for (rule, ms) in mappings.into_iter().enumerate() {
+                if !ms.is_empty() {
+                    per_rule[rule].push(SearchMatches {
+                        eclass: class.id,
+                        mappings: ms,
+                    });
+                }
+            }
+        }
+        per_rule
+    }
+}
+
+/// Patterns with a multi-wildcard, an `Or`, or a `TypedWildcard` can't be folded into a
+/// [`PatternProgram`]'s fixed columns, so [`MultiPattern`] matches them directly with
+/// [`search_pat`] instead.
+fn needs_naive_search<L>(pat: &Pattern<L>) -> bool {
+    match pat {
+        Pattern::Wildcard(_, WildcardKind::ZeroOrMore) => true,
+        Pattern::Wildcard(_, WildcardKind::Single) => false,
+        Pattern::Or(_) => true,
+        Pattern::TypedWildcard(..) => true,
+        Pattern::ENode(e) => e.children.iter().any(needs_naive_search),
+    }
+}
+
+/// Flags rule LHSs fully subsumed by an earlier, more general pattern in `rules` — e.g. a
+/// `(+ ?a 0)` added after `(+ ?a ?b)` can never match anything the earlier rule doesn't already
+/// catch. Implements Maranget-style usefulness over the pattern terms themselves, independent
+/// of any e-graph: `rules[i]` is reported when it's not useful w.r.t. `rules[..i]`.
+///
+/// An `Or` pattern is expanded into its alternatives before the check, since the usefulness
+/// algorithm below operates on plain constructor/wildcard columns; `rules[i]` is only flagged
+/// when *every* one of its alternatives is already covered by the earlier rows.
+///
+/// A `ZeroOrMore` wildcard is out of scope for this lint (see [`contains_multi_wildcard`]): a
+/// rule containing one anywhere is never flagged and never contributes coverage to later rules.
+///
+/// So is a wildcard name repeated within one alternative, e.g. `(/ ?a ?a)` (see
+/// [`contains_repeated_wildcard`]): `useful`/`specialize` treat each occurrence as an
+/// independent column, so a repeated name in an earlier row would make that row look like it
+/// covers e-classes it actually rejects (e.g. `(/ ?a ?a)` would look equivalent to `(/ ?a ?b)`),
+/// over-reporting a later, genuinely-distinct rule as redundant.
+///
+/// So is a `TypedWildcard` (see [`contains_typed_wildcard`]): its filter can't be decided
+/// statically, and treating it as unconstrained in an earlier row — rather than rejecting it
+/// outright — over-approximates what that row covers, the same unsafe direction as the two cases
+/// above.
+pub fn redundant_patterns<L: Language>(rules: &[Pattern<L>]) -> Vec<usize> {
+    let mut prior: Vec<Vec<Pattern<L>>> = Vec::new();
+    let mut redundant = Vec::new();
+    for (i, pat) in rules.iter().enumerate() {
+        if contains_multi_wildcard(pat) || contains_typed_wildcard(pat) {
+            continue;
+        }
+        let alternatives = expand_ors(pat);
+        if alternatives.iter().any(contains_repeated_wildcard) {
+            // dropping only the offending alternative would let the *other*, analyzable
+            // alternatives still be checked for usefulness — but an alternative excluded here
+            // might be exactly the one providing this rule's real coverage, so a rule is only
+            // safe to analyze (or contribute to `prior`) when every alternative is analyzable
+            continue;
+        }
+        let is_useful = alternatives
+            .iter()
+            .any(|alt| useful(std::slice::from_ref(alt), &prior));
+        if !is_useful {
+            redundant.push(i);
+        }
+        prior.extend(alternatives.into_iter().map(|alt| vec![alt]));
+    }
+    redundant
+}
+
+/// A wildcard name used more than once within `pat` (e.g. `(/ ?a ?a)`) ties its two occurrences
+/// to the same e-class, which the fixed-column matrix `useful`/`specialize`/`default_row`
+/// operate on can't express — each column there is matched independently of the others. Patterns
+/// with a repeated wildcard name are excluded from the redundancy lint entirely, the same escape
+/// hatch already applied to multi-wildcards in [`contains_multi_wildcard`].
+fn contains_repeated_wildcard<L>(pat: &Pattern<L>) -> bool {
+    let mut seen = Vec::new();
+    has_repeated_wildcard(pat, &mut seen)
+}
+
+fn has_repeated_wildcard<L>(pat: &Pattern<L>, seen: &mut Vec<QuestionMarkName>) -> bool {
+    match pat {
+        Pattern::Wildcard(w, _) | Pattern::TypedWildcard(w, _, _) => {
+            if seen.contains(w) {
+                true
+            } else {
+                seen.push(w.clone());
+                false
+            }
+        }
+        // each alternative binds independently, so a name reused across *different*
+        // alternatives (but not repeated within any single one) isn't actually nonlinear;
+        // check each alternative against its own copy of `seen` rather than a shared one
+        Pattern::Or(alts) => alts
+            .iter()
+            .any(|alt| has_repeated_wildcard(alt, &mut seen.clone())),
+        Pattern::ENode(e) => e.children.iter().any(|c| has_repeated_wildcard(c, seen)),
+    }
+}
+
+/// A `ZeroOrMore` wildcard has variable arity, which doesn't fit the fixed-column matrix
+/// `useful`/`specialize`/`default_row` operate on (each column there is exactly one sibling
+/// position). Rather than give it an under- or over-approximate column semantics, patterns
+/// containing one are excluded from the redundancy lint entirely.
+fn contains_multi_wildcard<L>(pat: &Pattern<L>) -> bool {
+    match pat {
+        Pattern::Wildcard(_, WildcardKind::ZeroOrMore) => true,
+        Pattern::Wildcard(_, WildcardKind::Single) => false,
+        // a `ZeroOrMore` `TypedWildcard` is caught by `contains_typed_wildcard` instead, since
+        // every `TypedWildcard` is out of scope for this lint regardless of kind
+        Pattern::TypedWildcard(..) => false,
+        Pattern::Or(alts) => alts.iter().any(contains_multi_wildcard),
+        Pattern::ENode(e) => e.children.iter().any(contains_multi_wildcard),
+    }
+}
+
+/// A `TypedWildcard`'s filter can't be decided statically against a constructor it wasn't
+/// written for, so a row containing one can't be soundly treated as unconstrained — doing so in
+/// an *earlier* row over-approximates what that row covers, making a later, genuinely-distinct
+/// rule look redundant. Patterns containing a `TypedWildcard` of either kind are excluded from
+/// the redundancy lint entirely, the same escape hatch as [`contains_multi_wildcard`].
+fn contains_typed_wildcard<L>(pat: &Pattern<L>) -> bool {
+    match pat {
+        Pattern::Wildcard(..) => false,
+        Pattern::TypedWildcard(..) => true,
+        Pattern::Or(alts) => alts.iter().any(contains_typed_wildcard),
+        Pattern::ENode(e) => e.children.iter().any(contains_typed_wildcard),
+    }
+}
+
+/// Expands every `Or` inside `pat` into the cartesian product of its alternatives, returning an
+/// equivalent set of `Or`-free patterns.
+fn expand_ors<L: Language>(pat: &Pattern<L>) -> Vec<Pattern<L>> {
+    match pat {
+        Pattern::Wildcard(_, _) => vec![pat.clone()],
+        Pattern::TypedWildcard(..) => vec![pat.clone()],
+        Pattern::Or(alts) => alts.iter().flat_map(expand_ors).collect(),
+        Pattern::ENode(e) => {
+            let child_options: Vec<Vec<Pattern<L>>> = e.children.iter().map(expand_ors).collect();
+            if child_options.is_empty() {
+                return vec![pat.clone()];
+            }
+            child_options
+                .into_iter()
+                .multi_cartesian_product()
+                .map(|children| Pattern::ENode(Box::new(ENode::new(e.op.clone(), children))))
+                .collect()
+        }
+    }
+}
+
+/// `q` is useful w.r.t. the earlier rows `p` if it can match some term none of `p` matches.
+/// Zero columns left: useful iff no earlier row survived down to here either.
+fn useful<L: Language>(q: &[Pattern<L>], p: &[Vec<Pattern<L>>]) -> bool {
+    match q.first() {
+        None => p.is_empty(),
+        Some(Pattern::ENode(e)) => {
+            let arity = e.children.len();
+            let specialized_p: Vec<_> = p
+                .iter()
+                .filter_map(|row| specialize(row, &e.op, arity))
+                .collect();
+            let specialized_q =
+                specialize(q, &e.op, arity).expect("q's own head always specializes");
+            useful(&specialized_q, &specialized_p)
+        }
+        Some(Pattern::Wildcard(_, WildcardKind::Single)) => {
+            // `L` is open-ended (e.g. arbitrary symbols), so we can never prove the operators
+            // seen in `p`'s leading column exhaust every value the column's type could take —
+            // we always fall back to the default matrix, i.e. the incomplete-signature case of
+            // Maranget's algorithm.
+            let default_q = q[1..].to_vec();
+            let default_p: Vec<_> = p.iter().filter_map(|row| default_row(row)).collect();
+            useful(&default_q, &default_p)
+        }
+        Some(Pattern::Wildcard(_, WildcardKind::ZeroOrMore)) => {
+            unreachable!("multi-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Some(Pattern::TypedWildcard(..)) => {
+            unreachable!("typed-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Some(Pattern::Or(_)) => unreachable!("Or patterns are expanded before usefulness checks"),
+    }
+}
+
+/// Specializes `row` for constructor `op`/`arity`: keeps it only if its leading column could
+/// still match that constructor, expanding that column into `arity` fresh columns (its actual
+/// children for a matching `ENode`, unconstrained fresh wildcards for a row that only had a
+/// wildcard there).
+fn specialize<L: Language>(row: &[Pattern<L>], op: &L, arity: usize) -> Option<Vec<Pattern<L>>> {
+    match row.first()? {
+        Pattern::ENode(e) if e.op == *op && e.children.len() == arity => {
+            let mut cols = e.children.clone();
+            cols.extend(row[1..].iter().cloned());
+            Some(cols)
+        }
+        Pattern::Wildcard(_, WildcardKind::Single) => {
+            let mut cols: Vec<_> = (0..arity).map(|_| fresh_wildcard()).collect();
+            cols.extend(row[1..].iter().cloned());
+            Some(cols)
+        }
+        Pattern::Wildcard(_, WildcardKind::ZeroOrMore) => {
+            unreachable!("multi-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Pattern::TypedWildcard(..) => {
+            unreachable!("typed-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Pattern::ENode(_) => None,
+        Pattern::Or(_) => unreachable!("Or patterns are expanded before usefulness checks"),
+    }
+}
+
+/// The default matrix: rows whose leading column is a (non-multi, untyped) wildcard, with that
+/// column dropped. `row` can never start with a `ZeroOrMore` or typed wildcard — see
+/// [`contains_multi_wildcard`] and [`contains_typed_wildcard`].
+fn default_row<L: Language>(row: &[Pattern<L>]) -> Option<Vec<Pattern<L>>> {
+    match row.first()? {
+        Pattern::Wildcard(_, WildcardKind::Single) => Some(row[1..].to_vec()),
+        Pattern::Wildcard(_, WildcardKind::ZeroOrMore) => {
+            unreachable!("multi-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Pattern::TypedWildcard(..) => {
+            unreachable!("typed-wildcard patterns are filtered out by `redundant_patterns`")
+        }
+        Pattern::ENode(_) => None,
+        Pattern::Or(_) => unreachable!("Or patterns are expanded before usefulness checks"),
+    }
+}
+
+fn fresh_wildcard<L>() -> Pattern<L> {
+    Pattern::Wildcard(
+        "?_".parse().expect("\"?_\" is a valid wildcard name"),
+        WildcardKind::Single,
+    )
+}
+
+/// Checks that every wildcard `rhs` reads is bound by *every* alternative of any `Or` in `lhs`.
+///
+/// Callers building a rule out of an `Or` LHS and a [`Pattern`] RHS must call this themselves at
+/// rule-construction time and reject the rule on `Err` — the crate's own rule constructors
+/// (such as `rewrite!`) don't call it, so nothing enforces it automatically. Skipping it
+/// doesn't panic: an RHS that reads a wildcard an alternative didn't bind just instantiates to no
+/// new e-classes at apply time (see `apply_pat`'s fallback), silently firing the rule as a no-op
+/// instead of producing the rewrite the caller intended.
+pub fn check_or_bindings<L>(lhs: &Pattern<L>, rhs: &Pattern<L>) -> Result<(), String> {
+    let mut bound = Vec::new();
+    always_bound(lhs, &mut bound);
+    let mut used = Vec::new();
+    used_wildcards(rhs, &mut used);
+    for w in used {
+        if !bound.contains(&w) {
+            return Err(format!(
+                "wildcard {:?} is used on the RHS but isn't bound by every alternative \
+                 of an Or pattern in the LHS",
+                w
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Wildcard names guaranteed to be bound no matter which `Or` alternative in `pat` actually
+/// matched: the union across an `ENode`'s children (all of them always match), but only the
+/// intersection across an `Or`'s alternatives (only one of them matches at a time).
+fn always_bound<L>(pat: &Pattern<L>, names: &mut Vec<QuestionMarkName>) {
+    match pat {
+        Pattern::Wildcard(w, _) | Pattern::TypedWildcard(w, _, _) => {
+            if !names.contains(w) {
+                names.push(w.clone());
+            }
+        }
+        Pattern::ENode(e) => {
+            for child in &e.children {
+                always_bound(child, names);
+            }
+        }
+        Pattern::Or(alts) => {
+            let mut alts = alts.iter();
+            let mut common = match alts.next() {
+                Some(first) => {
+                    let mut v = Vec::new();
+                    always_bound(first, &mut v);
+                    v
+                }
+                None => return,
+            };
+            for alt in alts {
+                let mut v = Vec::new();
+                always_bound(alt, &mut v);
+                common.retain(|w| v.contains(w));
+            }
+            for w in common {
+                if !names.contains(&w) {
+                    names.push(w);
+                }
+            }
+        }
+    }
+}
+
+fn used_wildcards<L>(pat: &Pattern<L>, names: &mut Vec<QuestionMarkName>) {
+    match pat {
+        Pattern::Wildcard(w, _) | Pattern::TypedWildcard(w, _, _) => {
+            if !names.contains(w) {
+                names.push(w.clone());
+            }
+        }
+        Pattern::ENode(e) => {
+            for child in &e.children {
+                used_wildcards(child, names);
+            }
+        }
+        Pattern::Or(alts) => {
+            for alt in alts {
+                used_wildcards(alt, names);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -383,4 +1121,309 @@ mod tests {
         let (_, best) = ext.find_best(2);
         eprintln!("Best: {:#?}", best);
     }
+
+    #[test]
+    fn multi_pattern_nonlinear_wildcard() {
+        // `(+ ?a ?a)` may only match operands in the same e-class; the `PatternProgram`
+        // decision tree has to reject a repeated wildcard binding two different e-classes, the
+        // same as `search_pat` already does.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let x = egraph.add(e!("x"));
+        let y = egraph.add(e!("y"));
+        let same = egraph.add(e!("+", x, x));
+        let diff = egraph.add(e!("+", x, y));
+        egraph.rebuild();
+
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let pat = Pattern::ENode(Box::new(e!("+", wc(&a), wc(&a))));
+
+        let multi = MultiPattern::compile(&[pat]);
+        let matches = multi.search(&egraph);
+        assert_eq!(matches.len(), 1);
+
+        let matched_classes: Vec<Id> = matches[0].iter().map(|m| m.eclass).collect();
+        assert_eq!(matched_classes, vec![same]);
+        assert!(!matched_classes.contains(&diff));
+    }
+
+    #[test]
+    fn multi_pattern_thrice_repeated_wildcard() {
+        // A wildcard repeated a third time must also be rejected once it mismatches, even
+        // though the rule is by then already absent from `eval`'s `live` list for this branch
+        // (a mismatch past the first repeat must not panic, just fail to match).
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let x = egraph.add(e!("x"));
+        let y = egraph.add(e!("y"));
+        let z = egraph.add(e!("z"));
+        let same = egraph.add(e!("+", x, x, x));
+        let diff = egraph.add(e!("+", x, y, z));
+        egraph.rebuild();
+
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let pat = Pattern::ENode(Box::new(e!("+", wc(&a), wc(&a), wc(&a))));
+
+        let multi = MultiPattern::compile(&[pat]);
+        let matches = multi.search(&egraph);
+        assert_eq!(matches.len(), 1);
+
+        let matched_classes: Vec<Id> = matches[0].iter().map(|m| m.eclass).collect();
+        assert_eq!(matched_classes, vec![same]);
+        assert!(!matched_classes.contains(&diff));
+    }
+
+    #[test]
+    fn conditional_applier_checks_condition() {
+        // `(f ?a ?b)` matches both `(f x x)` and `(f x y)`, but a `ConditionalApplier` guarded
+        // on `?a == ?b` should only produce a new e-class for the former.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let x = egraph.add(e!("x"));
+        let y = egraph.add(e!("y"));
+        let same = egraph.add(e!("f", x, x));
+        let diff = egraph.add(e!("f", x, y));
+        egraph.rebuild();
+
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let lhs = Pattern::ENode(Box::new(e!("f", wc(&a), wc(&b))));
+        let applier = ConditionalApplier {
+            condition: ConditionEqual(wc(&a), wc(&b)),
+            applier: wc(&a),
+        };
+
+        let matches = lhs.search(&egraph);
+        for m in &matches {
+            for subst in &m.mappings {
+                let out = applier.apply_one(&mut egraph, m.eclass, subst);
+                if m.eclass == same {
+                    assert_eq!(out, vec![x]);
+                } else if m.eclass == diff {
+                    assert!(out.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn redundant_patterns_flags_subsumed_rule() {
+        // `(+ ?a 0)` can never match anything `(+ ?a ?b)` doesn't already match, but only once
+        // the more general rule has actually been seen first.
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let general = Pattern::ENode(Box::new(e!("+", wc(&a), wc(&b))));
+        let specific =
+            Pattern::ENode(Box::new(e!("+", wc(&a), Pattern::ENode(Box::new(e!("0"))))));
+
+        let redundant = redundant_patterns(&[general.clone(), specific.clone()]);
+        assert_eq!(redundant, vec![1]);
+
+        let redundant = redundant_patterns(&[specific, general]);
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn redundant_patterns_ignores_multi_wildcards() {
+        // a `ZeroOrMore` wildcard is out of scope for this lint (see
+        // `contains_multi_wildcard`), so it must neither be flagged itself nor mask a later rule.
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let multi = Pattern::ENode(Box::new(e!(
+            "+",
+            Pattern::Wildcard(a, WildcardKind::ZeroOrMore)
+        )));
+        let single = Pattern::ENode(Box::new(e!("+", wc(&b))));
+
+        let redundant = redundant_patterns(&[multi, single]);
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn redundant_patterns_ignores_nonlinear_wildcards() {
+        // `(/ ?a ?a)` only matches a numerator equal to its denominator, so `(/ ?a ?b)` is
+        // strictly more general and must never be flagged as redundant against it.
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let nonlinear = Pattern::ENode(Box::new(e!("/", wc(&a), wc(&a))));
+        let general = Pattern::ENode(Box::new(e!("/", wc(&a), wc(&b))));
+
+        let redundant = redundant_patterns(&[nonlinear, general]);
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn redundant_patterns_excludes_whole_or_when_one_alternative_is_nonlinear() {
+        // `Or[(/ ?a ?a), (+ ?x ?y)]`'s second alternative is individually subsumed by a prior
+        // `(+ ?p ?q)`, but its first alternative is genuinely new coverage. Dropping only the
+        // nonlinear alternative and checking the rest would wrongly flag this rule as redundant;
+        // the whole rule must be excluded from the lint instead.
+        let p: QuestionMarkName = "?p".parse().unwrap();
+        let q: QuestionMarkName = "?q".parse().unwrap();
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let x: QuestionMarkName = "?x".parse().unwrap();
+        let y: QuestionMarkName = "?y".parse().unwrap();
+        let prior = Pattern::ENode(Box::new(e!("+", wc(&p), wc(&q))));
+        let or_rule = Pattern::Or(vec![
+            Pattern::ENode(Box::new(e!("/", wc(&a), wc(&a)))),
+            Pattern::ENode(Box::new(e!("+", wc(&x), wc(&y)))),
+        ]);
+
+        let redundant = redundant_patterns(&[prior, or_rule]);
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn redundant_patterns_ignores_typed_wildcards() {
+        // `(f ?n:const)` only matches a constant argument, so `(f ?m)` is strictly more general
+        // and must never be flagged as redundant against it.
+        let n: QuestionMarkName = "?n".parse().unwrap();
+        let m: QuestionMarkName = "?m".parse().unwrap();
+        let typed = Pattern::ENode(Box::new(e!(
+            "f",
+            Pattern::TypedWildcard(n, WildcardKind::Single, OpFilter::constant())
+        )));
+        let untyped = Pattern::ENode(Box::new(e!("f", wc(&m))));
+
+        let redundant = redundant_patterns(&[typed, untyped]);
+        assert!(redundant.is_empty());
+    }
+
+    #[test]
+    fn interior_multi_wildcard_matches_middle_slice() {
+        // `(f ?a ?*mid ?b)` puts the multi-wildcard between two fixed wildcards, so it should
+        // bind only the children strictly between the first and the last.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let x = egraph.add(e!("x"));
+        let y = egraph.add(e!("y"));
+        let z = egraph.add(e!("z"));
+        let w = egraph.add(e!("w"));
+        let f = egraph.add(e!("f", x, y, z, w));
+        egraph.rebuild();
+
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let mid: QuestionMarkName = "?mid".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let pat = Pattern::ENode(Box::new(e!(
+            "f",
+            wc(&a),
+            Pattern::Wildcard(mid.clone(), WildcardKind::ZeroOrMore),
+            wc(&b)
+        )));
+
+        let matches = pat.search(&egraph);
+        assert_eq!(matches.len(), 1);
+        let sm = &matches[0];
+        assert_eq!(sm.eclass, f);
+        assert_eq!(sm.mappings.len(), 1);
+
+        let wm = &sm.mappings[0];
+        assert_eq!(&wm[&a], &[x]);
+        assert_eq!(&wm[&mid], &[y, z]);
+        assert_eq!(&wm[&b], &[w]);
+    }
+
+    #[test]
+    fn or_pattern_matches_either_alternative() {
+        // `(neg (| (+ ?a ?b) (* ?a ?b)))` should match both a negated sum and a negated product.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let x = egraph.add(e!("x"));
+        let y = egraph.add(e!("y"));
+        let plus = egraph.add(e!("+", x, y));
+        let times = egraph.add(e!("*", x, y));
+        let neg_plus = egraph.add(e!("neg", plus));
+        let neg_times = egraph.add(e!("neg", times));
+        egraph.rebuild();
+
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        let inner = Pattern::Or(vec![
+            Pattern::ENode(Box::new(e!("+", wc(&a), wc(&b)))),
+            Pattern::ENode(Box::new(e!("*", wc(&a), wc(&b)))),
+        ]);
+        let pat = Pattern::ENode(Box::new(e!("neg", inner)));
+
+        let matches = pat.search(&egraph);
+        let matched_classes: Vec<Id> = matches.iter().map(|m| m.eclass).collect();
+        assert_eq!(matched_classes.len(), 2);
+        assert!(matched_classes.contains(&neg_plus));
+        assert!(matched_classes.contains(&neg_times));
+    }
+
+    #[test]
+    fn check_or_bindings_rejects_partially_bound_wildcard() {
+        let a: QuestionMarkName = "?a".parse().unwrap();
+        let b: QuestionMarkName = "?b".parse().unwrap();
+        // `?a` is bound by every alternative, `?b` only by the first
+        let lhs = Pattern::Or(vec![Pattern::ENode(Box::new(e!("+", wc(&a), wc(&b)))), wc(&a)]);
+
+        assert!(check_or_bindings(&lhs, &wc(&a)).is_ok());
+        assert!(check_or_bindings(&lhs, &wc(&b)).is_err());
+    }
+
+    #[test]
+    fn apply_pat_degrades_gracefully_on_unbound_wildcard() {
+        // Reachable if an `Or` LHS/RHS pair skips `check_or_bindings`: the RHS must produce no
+        // e-classes rather than panic when a wildcard isn't in `mapping`.
+        let mut egraph = EGraph::<String, ()>::default();
+        let eclass = egraph.add(e!("x"));
+        let b: QuestionMarkName = "?b".parse().unwrap();
+
+        let out = wc(&b).apply_one(&mut egraph, eclass, &WildMap::default());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn typed_wildcard_matches_only_filtered_eclasses() {
+        // `(f ?x:const)` should only match an `f` whose argument e-class has a constant e-node.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let five = egraph.add(e!("5"));
+        let a = egraph.add(e!("a"));
+        let b = egraph.add(e!("b"));
+        let sum = egraph.add(e!("+", a, b));
+        let f_const = egraph.add(e!("f", five));
+        let f_sum = egraph.add(e!("f", sum));
+        egraph.rebuild();
+
+        let x: QuestionMarkName = "?x".parse().unwrap();
+        let pat = Pattern::ENode(Box::new(e!(
+            "f",
+            Pattern::TypedWildcard(x, WildcardKind::Single, OpFilter::constant())
+        )));
+
+        let matches = pat.search(&egraph);
+        let matched_classes: Vec<Id> = matches.iter().map(|m| m.eclass).collect();
+        assert_eq!(matched_classes, vec![f_const]);
+        assert!(!matched_classes.contains(&f_sum));
+    }
+
+    #[test]
+    fn typed_multi_wildcard_rejects_unfiltered_children() {
+        // a `ZeroOrMore` `TypedWildcard` must refuse to bind if any captured child fails the
+        // filter, not just silently drop the constraint.
+        let mut egraph = EGraph::<String, ()>::default();
+
+        let one = egraph.add(e!("1"));
+        let two = egraph.add(e!("2"));
+        let a = egraph.add(e!("a"));
+        let b = egraph.add(e!("b"));
+        let sum = egraph.add(e!("+", a, b));
+        let all_const = egraph.add(e!("list", one, two));
+        let mixed = egraph.add(e!("list", one, sum));
+        egraph.rebuild();
+
+        let xs: QuestionMarkName = "?xs".parse().unwrap();
+        let pat = Pattern::ENode(Box::new(e!(
+            "list",
+            Pattern::TypedWildcard(xs, WildcardKind::ZeroOrMore, OpFilter::constant())
+        )));
+
+        let matches = pat.search(&egraph);
+        let matched_classes: Vec<Id> = matches.iter().map(|m| m.eclass).collect();
+        assert_eq!(matched_classes, vec![all_const]);
+        assert!(!matched_classes.contains(&mixed));
+    }
 }